@@ -0,0 +1,74 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let git_hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+
+    // without these cargo never reruns the script after a commit and the hash goes stale;
+    // only existing paths are listed, a missing one would make every build dirty
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+
+        let ref_path = git(&["rev-parse", "--symbolic-full-name", "HEAD"])
+            .and_then(|head_ref| git(&["rev-parse", "--git-path", &head_ref]))
+            .filter(|path| Path::new(path).exists());
+        let packed_refs = format!("{}/packed-refs", git_dir);
+        match ref_path {
+            Some(path) => println!("cargo:rerun-if-changed={}", path),
+            None if Path::new(&packed_refs).exists() => {
+                println!("cargo:rerun-if-changed={}", packed_refs)
+            }
+            None => (),
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds from embedding the wall clock
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=RLOX_GIT_HASH={}", git_hash);
+    println!(
+        "cargo:rustc-env=RLOX_BUILD_DATE={}",
+        civil_date(seconds / 86_400)
+    );
+    println!(
+        "cargo:rustc-env=RLOX_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|text| text.trim().to_string())
+}
+
+// days since 1970-01-01 to YYYY-MM-DD, after Howard Hinnant's civil_from_days
+fn civil_date(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,141 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write;
+use std::fs::OpenOptions;
+use std::io::{self, Write as _};
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::{env, process};
+
+// bigger sources are left out of the bundle; the user can attach them by hand
+const MAX_BUNDLED_SOURCE: usize = 16 * 1024;
+
+struct Context {
+    phase: &'static str,
+    source: Option<String>,
+    source_len: usize,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    phase: "startup",
+    source: None,
+    source_len: 0,
+});
+
+pub fn install() {
+    panic::set_hook(Box::new(report_internal_error));
+}
+
+pub fn enter_phase(phase: &'static str, source: &str) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.phase = phase;
+        context.source_len = source.len();
+        context.source = if source.len() <= MAX_BUNDLED_SOURCE {
+            Some(source.to_string())
+        } else {
+            None
+        };
+    }
+}
+
+fn report_internal_error(info: &PanicHookInfo) {
+    eprintln!("[internal error] {}", info);
+
+    let bundle = build_bundle(info);
+    match write_bundle(&bundle) {
+        Ok(path) => {
+            eprintln!("This is a bug in rlox, not in your script. A crash report was written to");
+            eprintln!("  {}", path.display());
+            eprintln!("Please attach it to a bug report.");
+        }
+        Err(_) => {
+            eprintln!("This is a bug in rlox, not in your script. Please report it along with the source that triggered it.");
+        }
+    }
+}
+
+// create_new refuses existing files and symlinks, so a planted name in a shared
+// temp dir can't redirect the write; a taken name just moves on to the next one.
+// The bundle holds the user's source, so other users of that dir can't read it.
+fn write_bundle(bundle: &str) -> io::Result<PathBuf> {
+    let dir = env::temp_dir();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    for attempt in 0..100 {
+        let path = dir.join(format!("rlox-crash-{}-{}.txt", process::id(), attempt));
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(bundle.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no free crash report name",
+    ))
+}
+
+fn build_bundle(info: &PanicHookInfo) -> String {
+    // the panic may have happened while the context was being updated
+    let context = CONTEXT.try_lock().ok();
+    let context = context.as_deref();
+
+    let mut bundle = String::new();
+    let _ = writeln!(bundle, "rlox {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(bundle, "phase: {}", context.map_or("unknown", |c| c.phase));
+    let _ = writeln!(bundle, "panic: {}", info);
+    let _ = writeln!(bundle, "\nbacktrace:\n{}", Backtrace::force_capture());
+    match context {
+        Some(Context {
+            source: Some(source),
+            ..
+        }) => {
+            let _ = writeln!(bundle, "source:\n{}", source);
+        }
+        Some(context) if context.source_len > 0 => {
+            let _ = writeln!(bundle, "source: omitted ({} bytes)", context.source_len);
+        }
+        _ => {
+            let _ = writeln!(bundle, "source: none");
+        }
+    }
+
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_bundle;
+    use std::{env, fs, process};
+
+    #[cfg(unix)]
+    #[test]
+    fn write_bundle_does_not_follow_a_planted_symlink_and_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir();
+        let victim = dir.join(format!("rlox-crash-victim-{}.txt", process::id()));
+        let planted = dir.join(format!("rlox-crash-{}-0.txt", process::id()));
+        fs::write(&victim, "keep me").unwrap();
+        let _ = fs::remove_file(&planted);
+        std::os::unix::fs::symlink(&victim, &planted).unwrap();
+
+        let path = write_bundle("report").unwrap();
+
+        assert_ne!(path, planted);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "report");
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep me");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        for file in [&path, &planted, &victim] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// offsets are byte offsets into the source, as used by the scanner
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        for (offset, byte) in source.bytes().enumerate() {
+            if byte == b'\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+
+        LineIndex {
+            source,
+            line_starts,
+        }
+    }
+
+    // 1-based (line, column) of the byte at `offset`; columns count chars
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self
+            .source
+            .get(line_start..offset)
+            .map_or(offset - line_start, |text| text.chars().count());
+        (line, column + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineIndex;
+
+    #[test]
+    fn positions_are_one_based() {
+        let lines = LineIndex::new("ab\ncd\n");
+
+        assert_eq!(lines.position(0), (1, 1));
+        assert_eq!(lines.position(2), (1, 3));
+        assert_eq!(lines.position(3), (2, 1));
+        assert_eq!(lines.position(6), (3, 1));
+    }
+
+    #[test]
+    fn columns_count_chars_not_bytes() {
+        let source = "é\"x";
+        let lines = LineIndex::new(source);
+
+        assert_eq!(lines.position(source.find('"').unwrap()), (1, 2));
+    }
+}
@@ -1,12 +1,15 @@
-use scanner::Scanner;
+use scanner::{Scanner, DEFAULT_MAX_TOKEN_LENGTH};
+use source::{FileSystem, SourceProvider};
 use std::error::Error;
 use std::io::{Stdin, Write};
-use std::path::Path;
 use std::process::exit;
 use std::{env, io};
 use token_type::Token;
 
+mod crash;
+mod line_index;
 mod scanner;
+mod source;
 mod token_type;
 
 struct ErrorReporter {
@@ -21,47 +24,100 @@ impl ErrorReporter {
     fn set_error(&mut self) {
         self.had_error = true;
     }
+
+    fn reset(&mut self) {
+        self.had_error = false;
+    }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
+    crash::install();
+
     let args: Vec<String> = env::args().collect();
 
+    // a flag rather than a subcommand, so a script called `version` still runs
+    if args.len() > 1 && args[1] == "--version" {
+        let verbose = match args.get(2).map(String::as_str) {
+            None => false,
+            Some("--verbose") if args.len() == 3 => true,
+            _ => {
+                eprintln!("Usage: rlox --version [--verbose]");
+                exit(64);
+            }
+        };
+        if let Err(e) = print_version(verbose) {
+            eprintln!("Error: {}", e);
+            exit(74);
+        }
+        return;
+    }
+
     let mut error_reporter = ErrorReporter::new();
 
     if args.len() > 2 {
         eprintln!("Usage: rlox [script]");
+        eprintln!("       rlox --version [--verbose]");
         exit(64);
     } else if args.len() == 2 {
-        if let Err(e) = run_file(&args[1], &mut error_reporter) {
+        if let Err(e) = run_file(&FileSystem, &args[1], &mut error_reporter) {
             eprintln!("Error: {}", e);
-            error_reporter.set_error();
-        }
-    } else {
-        if let Err(e) = run_prompt(&mut error_reporter) {
-            eprintln!("Error: {}", e);
-            error_reporter.set_error();
+            exit(failure_exit_code(&*e));
         }
+    } else if let Err(e) = run_prompt(&mut error_reporter) {
+        eprintln!("Error: {}", e);
+        exit(failure_exit_code(&*e));
     }
 
     if error_reporter.had_error {
         exit(65);
     }
+}
+
+// undecodable input is bad data like a scan error, not an I/O failure
+fn failure_exit_code(e: &(dyn Error + 'static)) -> i32 {
+    match e.downcast_ref::<io::Error>() {
+        Some(e) if e.kind() == io::ErrorKind::InvalidData => 65,
+        _ => 74,
+    }
+}
+
+fn print_version(verbose: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    writeln!(out, "rlox {}", env!("CARGO_PKG_VERSION"))?;
+    if verbose {
+        writeln!(out, "commit: {}", env!("RLOX_GIT_HASH"))?;
+        writeln!(out, "built: {}", env!("RLOX_BUILD_DATE"))?;
+        writeln!(out, "target: {}", env!("RLOX_TARGET"))?;
+        writeln!(
+            out,
+            "profile: {}",
+            if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            }
+        )?;
+    }
 
     Ok(())
 }
 
-fn run_file(path: &str, error_reporter: &mut ErrorReporter) -> Result<(), Box<dyn Error>> {
-    let bytes = std::fs::read(Path::new(path))?;
-    let source = String::from_utf8(bytes)?;
-    run(&source, error_reporter);
+fn run_file(
+    provider: &dyn SourceProvider,
+    path: &str,
+    error_reporter: &mut ErrorReporter,
+) -> Result<(), Box<dyn Error>> {
+    let source = provider.load(path)?;
+    run((*source).as_ref(), error_reporter)?;
 
     Ok(())
 }
 
 fn run_prompt(error_reporter: &mut ErrorReporter) -> Result<(), Box<dyn Error>> {
     loop {
-        print!("> ");
-        io::stdout().flush()?;
+        let mut out = io::stdout();
+        write!(out, "> ")?;
+        out.flush()?;
 
         let stdin: Stdin = io::stdin();
         let mut input = String::new();
@@ -71,26 +127,71 @@ fn run_prompt(error_reporter: &mut ErrorReporter) -> Result<(), Box<dyn Error>>
             break;
         }
 
-        run(&input, error_reporter);
+        run(&input, error_reporter)?;
+        // a bad line shouldn't end the session or its exit status
+        error_reporter.reset();
     }
 
     Ok(())
 }
 
-fn run(source: &str, error_reporter: &mut ErrorReporter) {
-    let mut scanner: Scanner = Scanner::new(source.to_string(), error_reporter);
+// writes fail instead of panicking when stdout goes away, e.g. piped into `head`
+fn run(source: &str, error_reporter: &mut ErrorReporter) -> io::Result<()> {
+    crash::enter_phase("scan", source);
+    let mut scanner: Scanner = Scanner::new(source, DEFAULT_MAX_TOKEN_LENGTH, error_reporter);
     let tokens: &[Token] = scanner.scan_tokens();
 
+    let mut out = io::stdout().lock();
     for token in tokens {
-        println!("{:?}", token);
+        writeln!(out, "{:?}", token)?;
     }
+
+    Ok(())
 }
 
 fn error(error_reporter: &mut ErrorReporter, line_no: usize, message: &str) {
     report(error_reporter, line_no, "", message);
 }
 
+// extra context for the error just reported; doesn't count as another error
+fn note(line_no: usize, message: &str) {
+    eprintln!("[line {}] Note: {}", line_no, message);
+}
+
 fn report(error_reporter: &mut ErrorReporter, line_no: usize, location: &str, message: &str) {
     eprintln!("[line {}] Error{}: {}", line_no, location, message);
     error_reporter.set_error();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{run_file, ErrorReporter};
+    use std::collections::HashMap;
+    use std::io;
+
+    #[test]
+    fn run_file_reads_from_an_in_memory_provider() {
+        let sources = HashMap::from([
+            ("main.lox".to_string(), "print 1;".to_string()),
+            ("bad.lox".to_string(), "@".to_string()),
+        ]);
+        let mut reporter = ErrorReporter::new();
+
+        run_file(&sources, "main.lox", &mut reporter).unwrap();
+        assert!(!reporter.had_error);
+
+        run_file(&sources, "bad.lox", &mut reporter).unwrap();
+        assert!(reporter.had_error);
+    }
+
+    #[test]
+    fn missing_in_memory_source_is_not_found() {
+        let mut reporter = ErrorReporter::new();
+        let e = run_file(&HashMap::new(), "main.lox", &mut reporter).unwrap_err();
+
+        assert_eq!(
+            e.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::NotFound)
+        );
+    }
+}
@@ -1,23 +1,37 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use crate::{
     error,
+    line_index::LineIndex,
+    note,
     token_type::{Literal, Token, TokenType},
     ErrorReporter,
 };
 
+// generated sources can contain absurdly long literals; refuse them instead of
+// allocating a copy of each one
+pub const DEFAULT_MAX_TOKEN_LENGTH: usize = 1 << 16;
+
 pub struct Scanner<'a> {
-    source: Vec<char>,
+    source: &'a str,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
-    line_no: usize,
+    max_token_length: usize,
+    lines: LineIndex<'a>,
     keywords: HashMap<&'a str, TokenType>,
+    strings: HashSet<Arc<str>>,
     error_reporter: &'a mut ErrorReporter,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(source: String, error_reporter: &'a mut ErrorReporter) -> Self {
+    pub fn new(
+        source: &'a str,
+        max_token_length: usize,
+        error_reporter: &'a mut ErrorReporter,
+    ) -> Self {
         let mut keywords: HashMap<&'a str, TokenType> = HashMap::new();
 
         keywords.insert("and", TokenType::And);
@@ -38,99 +52,164 @@ impl<'a> Scanner<'a> {
         keywords.insert("while", TokenType::While);
 
         Scanner {
-            source: source.chars().collect(),
+            source,
             tokens: vec![],
             start: 0,
             current: 0,
-            line_no: 1,
+            max_token_length,
+            lines: LineIndex::new(source),
             keywords,
+            strings: HashSet::new(),
             error_reporter,
         }
     }
 
+    fn line_no(&self) -> usize {
+        self.lines.position(self.start).0
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
+    fn rest(&self) -> &'a str {
+        self.source.get(self.current..).unwrap_or("")
+    }
+
+    fn lexeme(&self) -> &'a str {
+        self.source.get(self.start..self.current).unwrap_or("")
+    }
+
     fn advance(&mut self) -> char {
-        let c = self.source[self.current];
-        self.current += 1;
-        c
+        match self.rest().chars().next() {
+            Some(c) => {
+                self.current += c.len_utf8();
+                c
+            }
+            None => '\0',
+        }
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
-        let text: String = self.source[self.start..self.current].iter().collect();
+        if self.token_too_long() {
+            return;
+        }
+        let text = self.intern(self.lexeme());
         self.tokens.push(Token::new(token_type, text, literal));
     }
 
-    fn next_match(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
+    fn token_too_long(&mut self) -> bool {
+        if self.current - self.start <= self.max_token_length {
             return false;
         }
-        if self.source[self.current] != expected {
+        let message = format!(
+            "Token is longer than the maximum of {} bytes.",
+            self.max_token_length
+        );
+        error(self.error_reporter, self.line_no(), &message);
+        true
+    }
+
+    fn next_match(&mut self, expected: char) -> bool {
+        if !self.rest().starts_with(expected) {
             return false;
         }
 
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source[self.current]
+        self.rest().chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
+        self.rest().chars().nth(1).unwrap_or('\0')
+    }
+
+    // repeated lexemes and literals share one allocation
+    fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(value) {
+            return Arc::clone(interned);
         }
-        self.source[self.current + 1]
+        let interned: Arc<str> = Arc::from(value);
+        self.strings.insert(Arc::clone(&interned));
+        interned
     }
 
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line_no += 1;
-            }
             self.advance();
         }
 
         if self.is_at_end() {
-            error(self.error_reporter, self.line_no, "Unterminated string.");
+            let (open_line, open_column) = self.lines.position(self.start);
+            let (end_line, _) = self.lines.position(self.current);
+            error(self.error_reporter, end_line, "Unterminated string.");
+            // a trailing newline (e.g. a REPL line) doesn't make it a multi-line string
+            let spans_lines = self.lexeme().trim_end_matches(['\n', '\r']).contains('\n');
+            let message = if spans_lines {
+                format!(
+                    "The string starting at column {} has no closing '\"'; add one at the end of line {} if it should end there.",
+                    open_column, open_line
+                )
+            } else {
+                format!(
+                    "The string starting at column {} has no closing '\"'; add one at the end of line {}.",
+                    open_column, open_line
+                )
+            };
+            note(open_line, &message);
             return;
         }
 
         // for the closing '"'
         self.advance();
 
-        let value: String = self.source[(self.start + 1)..(self.current - 1)]
-            .iter()
-            .collect();
+        if self.token_too_long() {
+            return;
+        }
+
+        let Some(body) = self
+            .lexeme()
+            .strip_prefix('"')
+            .and_then(|lexeme| lexeme.strip_suffix('"'))
+        else {
+            error(self.error_reporter, self.line_no(), "Unterminated string.");
+            return;
+        };
+        let value = match unescape(body) {
+            Ok(value) => value,
+            Err((offset, message)) => {
+                let (line, column) = self.lines.position(self.start + 1 + offset);
+                let message = format!("{} at column {}.", message, column);
+                error(self.error_reporter, line, &message);
+                return;
+            }
+        };
+        let value = self.intern(&value);
         self.add_token(TokenType::String, Some(Literal::Str(value)));
     }
 
     fn number(&mut self) {
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
-        let value: String = self.source[self.start..self.current].iter().collect();
-        match value.parse::<f64>() {
+        match self.lexeme().parse::<f64>() {
             Ok(num) => self.add_token(TokenType::Number, Some(Literal::Number(num))),
             Err(_) => {
                 error(
                     self.error_reporter,
-                    self.line_no,
+                    self.line_no(),
                     "Invalid numeric literal.",
                 );
             }
@@ -142,10 +221,20 @@ impl<'a> Scanner<'a> {
             self.advance();
         }
 
-        let text: String = self.source[self.start..self.current].iter().collect();
+        // without NFC normalization, 'Å' (U+212B) and 'Å' (U+00C5) would be two
+        // different bindings that look the same, so identifiers stay ASCII as in jlox
+        if !self.lexeme().is_ascii() {
+            let message = format!(
+                "Identifier '{}' contains non-ASCII characters; use only ASCII letters, digits and '_'.",
+                self.lexeme()
+            );
+            error(self.error_reporter, self.line_no(), &message);
+            return;
+        }
+
         let token_type = self
             .keywords
-            .get(text.as_str())
+            .get(self.lexeme())
             .unwrap_or(&TokenType::Identifier);
 
         self.add_token(token_type.clone(), None);
@@ -218,15 +307,14 @@ impl<'a> Scanner<'a> {
                 }
             }
             '"' => self.string(),
-            ' ' | '\r' | '\t' => (),
-            '\n' => self.line_no += 1,
+            ' ' | '\r' | '\t' | '\n' => (),
             _ => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.number();
                 } else if c.is_alphabetic() || c == '_' {
                     self.identifier();
                 } else {
-                    error(self.error_reporter, self.line_no, "Unexpected character.");
+                    error(self.error_reporter, self.line_no(), "Unexpected character.");
                 }
             }
         }
@@ -238,9 +326,229 @@ impl<'a> Scanner<'a> {
             self.scan_token();
         }
 
-        self.tokens
-            .push(Token::new(TokenType::EOF, String::new(), None));
+        let text = self.intern("");
+        self.tokens.push(Token::new(TokenType::EOF, text, None));
         &self.tokens
     }
 }
 
+// Two escapes: \\ for a backslash and \u{X} to \u{XXXXXX} for a code point; any
+// other backslash is kept as written. This departs from jlox, which keeps string
+// contents verbatim: there "a\\b" has two backslashes and "\u{D800}" is eight
+// plain characters, here the first has one and the second is an error.
+// Errors carry the byte offset of the offending backslash within `raw`.
+fn unescape(raw: &str) -> Result<Cow<'_, str>, (usize, &'static str)> {
+    if !raw.contains('\\') {
+        return Ok(Cow::Borrowed(raw));
+    }
+
+    let mut value = String::with_capacity(raw.len());
+    let mut rest = raw;
+
+    while let Some(index) = rest.find('\\') {
+        let offset = raw.len() - rest.len() + index;
+        value.push_str(&rest[..index]);
+
+        let after = &rest[index + 1..];
+        if let Some(after) = after.strip_prefix('\\') {
+            value.push('\\');
+            rest = after;
+            continue;
+        }
+        let Some(body) = after.strip_prefix("u{") else {
+            value.push('\\');
+            rest = after;
+            continue;
+        };
+
+        let end = body
+            .find('}')
+            .ok_or((offset, "Unterminated unicode escape"))?;
+        let digits = &body[..end];
+        if digits.is_empty() || digits.len() > 6 || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+            return Err((offset, "Invalid unicode escape"));
+        }
+
+        let code =
+            u32::from_str_radix(digits, 16).map_err(|_| (offset, "Invalid unicode escape"))?;
+        match char::from_u32(code) {
+            Some(c) => value.push(c),
+            None => return Err((offset, "Unicode escape is not a valid code point")),
+        }
+        rest = &body[end + 1..];
+    }
+    value.push_str(rest);
+
+    Ok(Cow::Owned(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unescape, Scanner, DEFAULT_MAX_TOKEN_LENGTH};
+    use crate::token_type::Literal;
+    use crate::ErrorReporter;
+    use std::sync::Arc;
+
+    fn scan_with_limit(source: &str, max_token_length: usize) -> (Vec<String>, bool) {
+        let mut reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(source, max_token_length, &mut reporter);
+        let lexemes = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| token.lexeme.to_string())
+            .collect();
+        (lexemes, reporter.had_error)
+    }
+
+    #[test]
+    fn token_at_the_length_limit_is_accepted() {
+        assert_eq!(
+            scan_with_limit("abcd", 4),
+            (vec!["abcd".into(), "".into()], false)
+        );
+        assert_eq!(
+            scan_with_limit("\"ab\"", 4),
+            (vec!["\"ab\"".into(), "".into()], false)
+        );
+    }
+
+    #[test]
+    fn token_one_byte_over_the_limit_is_rejected() {
+        assert_eq!(scan_with_limit("abcde", 4), (vec!["".into()], true));
+        assert_eq!(scan_with_limit("\"abc\"", 4), (vec!["".into()], true));
+    }
+
+    #[test]
+    fn scanning_continues_after_an_overlong_token() {
+        assert_eq!(
+            scan_with_limit("abcde x;", 4),
+            (vec!["x".into(), ";".into(), "".into()], true)
+        );
+    }
+
+    #[test]
+    fn identical_literals_share_one_allocation() {
+        let mut reporter = ErrorReporter::new();
+        let mut scanner =
+            Scanner::new("\"same\" \"same\"", DEFAULT_MAX_TOKEN_LENGTH, &mut reporter);
+        let tokens = scanner.scan_tokens();
+
+        assert!(Arc::ptr_eq(&tokens[0].lexeme, &tokens[1].lexeme));
+        match (&tokens[0].literal, &tokens[1].literal) {
+            (Some(Literal::Str(first)), Some(Literal::Str(second))) => {
+                assert!(Arc::ptr_eq(first, second))
+            }
+            other => panic!("expected two string literals, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn non_ascii_identifiers_are_rejected() {
+        for source in [
+            "\u{212B}",
+            "\u{C5}",
+            "caf\u{E9}",
+            "\u{1100}\u{1161}",
+            "\u{AC00}",
+        ] {
+            let mut reporter = ErrorReporter::new();
+            let mut scanner = Scanner::new(source, DEFAULT_MAX_TOKEN_LENGTH, &mut reporter);
+            let tokens = scanner.scan_tokens();
+
+            assert_eq!(tokens.len(), 1, "{:?} should only leave EOF", source);
+            assert!(reporter.had_error, "{:?} should be an error", source);
+        }
+    }
+
+    #[test]
+    fn ascii_identifiers_are_accepted() {
+        let mut reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(
+            "_snake_case2 camelCase",
+            DEFAULT_MAX_TOKEN_LENGTH,
+            &mut reporter,
+        );
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(&*tokens[0].lexeme, "_snake_case2");
+        assert_eq!(&*tokens[1].lexeme, "camelCase");
+        assert!(!reporter.had_error);
+    }
+
+    #[test]
+    fn decodes_unicode_escapes() {
+        assert_eq!(unescape(r"a\u{1F600}b").unwrap(), "a\u{1F600}b");
+        assert_eq!(unescape(r"\u{e9}\u{10FFFF}").unwrap(), "\u{e9}\u{10FFFF}");
+    }
+
+    #[test]
+    fn escaped_backslash_is_one_backslash() {
+        assert_eq!(unescape(r"a\\b").unwrap(), r"a\b");
+        assert_eq!(unescape(r"\\u{41}").unwrap(), r"\u{41}");
+        assert_eq!(unescape(r"\\\u{41}").unwrap(), r"\A");
+        assert_eq!(unescape(r"\\\\").unwrap(), r"\\");
+    }
+
+    #[test]
+    fn escapes_are_decoded_into_the_literal() {
+        let mut reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(
+            r#""x\u{E9}\\u{41}""#,
+            DEFAULT_MAX_TOKEN_LENGTH,
+            &mut reporter,
+        );
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(&*tokens[0].lexeme, r#""x\u{E9}\\u{41}""#);
+        match &tokens[0].literal {
+            Some(Literal::Str(value)) => assert_eq!(&**value, "x\u{E9}\\u{41}"),
+            other => panic!("expected a string literal, got {:?}", other),
+        }
+        assert!(!reporter.had_error);
+    }
+
+    #[test]
+    fn keeps_other_backslashes_as_written() {
+        assert_eq!(unescape(r"C:\users\bob").unwrap(), r"C:\users\bob");
+        assert_eq!(unescape(r"\u41 \x").unwrap(), r"\u41 \x");
+        assert_eq!(unescape(r"\u").unwrap(), r"\u");
+    }
+
+    #[test]
+    fn rejects_empty_braces() {
+        assert_eq!(unescape(r"ab\u{}"), Err((2, "Invalid unicode escape")));
+    }
+
+    #[test]
+    fn rejects_more_than_six_digits() {
+        assert_eq!(unescape(r"\u{0000041}"), Err((0, "Invalid unicode escape")));
+    }
+
+    #[test]
+    fn rejects_surrogates() {
+        assert_eq!(
+            unescape(r"\u{D800}"),
+            Err((0, "Unicode escape is not a valid code point"))
+        );
+        assert_eq!(
+            unescape(r"x\u{DFFF}"),
+            Err((1, "Unicode escape is not a valid code point"))
+        );
+    }
+
+    #[test]
+    fn rejects_code_points_above_10ffff() {
+        assert_eq!(
+            unescape(r"\u{110000}"),
+            Err((0, "Unicode escape is not a valid code point"))
+        );
+    }
+
+    #[test]
+    fn rejects_an_unclosed_escape() {
+        assert_eq!(
+            unescape(r"ok \u{41"),
+            Err((3, "Unterminated unicode escape"))
+        );
+    }
+}
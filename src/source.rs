@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+use std::{fs, io, path::Path};
+
+// boxed so a provider can hand out borrowed or mapped text instead of a copy
+pub trait SourceProvider {
+    fn load<'s>(&'s self, path: &str) -> io::Result<Box<dyn AsRef<str> + 's>>;
+}
+
+pub struct FileSystem;
+
+impl SourceProvider for FileSystem {
+    fn load<'s>(&'s self, path: &str) -> io::Result<Box<dyn AsRef<str> + 's>> {
+        Ok(Box::new(fs::read_to_string(Path::new(path))?))
+    }
+}
+
+// in-memory sources keyed by path, lent out without copying
+impl SourceProvider for HashMap<String, String> {
+    fn load<'s>(&'s self, path: &str) -> io::Result<Box<dyn AsRef<str> + 's>> {
+        match self.get(path) {
+            Some(source) => Ok(Box::new(source.as_str())),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no source named '{}'", path),
+            )),
+        }
+    }
+}
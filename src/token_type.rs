@@ -1,5 +1,8 @@
 use std::fmt;
+use std::sync::Arc;
 
+// variant names are printed in token dumps, keep EOF as in the book
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug)]
 pub enum TokenType {
     LeftParen,
@@ -96,7 +99,7 @@ impl fmt::Display for TokenType {
 #[derive(Debug)]
 pub enum Literal {
     Number(f64),
-    Str(String),
+    Str(Arc<str>),
 }
 
 impl fmt::Display for Literal {
@@ -111,12 +114,12 @@ impl fmt::Display for Literal {
 #[derive(Debug)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Arc<str>,
     pub literal: Option<Literal>,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: Option<Literal>) -> Self {
+    pub fn new(token_type: TokenType, lexeme: Arc<str>, literal: Option<Literal>) -> Self {
         Token {
             token_type,
             lexeme,
@@ -0,0 +1,221 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn rlox() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_crafting_interpreters"))
+}
+
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("rlox-cli-{}-{}.lox", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn run_prompt(input: &str) -> Output {
+    let mut child = rlox()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn too_many_arguments_is_a_usage_error() {
+    let output = rlox().args(["a.lox", "b.lox"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).contains("Usage: rlox [script]"));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn version_with_bad_arguments_is_a_usage_error() {
+    for args in [
+        &["--version", "--bogus"][..],
+        &["--version", "--verbose", "x"],
+    ] {
+        let output = rlox().args(args).output().unwrap();
+
+        assert_eq!(output.status.code(), Some(64));
+        assert!(stderr(&output).contains("Usage: rlox --version [--verbose]"));
+        assert!(stdout(&output).is_empty());
+    }
+}
+
+#[test]
+fn version_prints_the_package_version() {
+    let output = rlox().arg("--version").output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        format!("rlox {}\n", env!("CARGO_PKG_VERSION"))
+    );
+    assert!(stderr(&output).is_empty());
+}
+
+#[test]
+fn verbose_version_prints_build_details() {
+    let output = rlox().args(["--version", "--verbose"]).output().unwrap();
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(lines[0], format!("rlox {}", env!("CARGO_PKG_VERSION")));
+    for (line, label) in lines[1..]
+        .iter()
+        .zip(["commit: ", "built: ", "target: ", "profile: "])
+    {
+        assert!(
+            line.starts_with(label),
+            "{:?} should start with {:?}",
+            line,
+            label
+        );
+    }
+    assert_eq!(lines.len(), 5);
+}
+
+#[test]
+fn script_named_version_is_run() {
+    let dir = env::temp_dir().join(format!("rlox-cli-{}-version-dir", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("version"), "print 1;\n").unwrap();
+    let output = rlox().current_dir(&dir).arg("version").output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("lexeme: \"print\""));
+}
+
+#[test]
+fn scan_error_exits_65_with_diagnostic_on_stderr() {
+    let path = script("scan-error", "var x = @;\n");
+    let output = rlox().arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stderr(&output), "[line 1] Error: Unexpected character.\n");
+    assert!(!stdout(&output).contains("Error"));
+    assert!(stdout(&output).contains("token_type: EOF"));
+}
+
+#[test]
+fn clean_script_exits_0() {
+    let path = script("clean", "print 1;\n");
+    let output = rlox().arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).is_empty());
+}
+
+#[test]
+fn missing_file_exits_74() {
+    let path = env::temp_dir().join("rlox-cli-does-not-exist.lox");
+    let output = rlox().arg(&path).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(74));
+    assert!(stderr(&output).starts_with("Error: "));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn non_utf8_script_exits_65() {
+    let path = env::temp_dir().join(format!("rlox-cli-{}-non-utf8.lox", std::process::id()));
+    fs::write(&path, b"print \"\xff\";\n").unwrap();
+    let output = rlox().arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).starts_with("Error: "));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn directory_exits_74() {
+    let output = rlox().arg(env::temp_dir()).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(74));
+    assert!(stderr(&output).starts_with("Error: "));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn prompt_keeps_going_after_a_bad_line() {
+    let output = run_prompt("@\nprint 1;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "[line 1] Error: Unexpected character.\n");
+    assert!(!stdout(&output).contains("Error"));
+    assert!(stdout(&output).contains("lexeme: \"print\""));
+}
+
+#[test]
+fn unterminated_string_keeps_book_format_and_notes_the_opening_quote() {
+    let path = script("unterminated", "print 1;\nvar s = \"abc\ndef");
+    let output = rlox().arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 3] Error: Unterminated string.\n\
+         [line 2] Note: The string starting at column 9 has no closing '\"'; \
+         add one at the end of line 2 if it should end there.\n"
+    );
+}
+
+#[test]
+fn unterminated_single_line_string_suggests_closing_it_on_that_line() {
+    let output = run_prompt("print \"hello\n");
+
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error: Unterminated string.\n\
+         [line 1] Note: The string starting at column 7 has no closing '\"'; \
+         add one at the end of line 1.\n"
+    );
+}
+
+#[test]
+fn backslash_u_without_brace_is_kept_as_written() {
+    let path = script("windows-path", "print \"C:\\users\\bob\";\n");
+    let output = rlox().arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).is_empty());
+}
+
+#[test]
+fn bad_escape_is_reported_at_its_own_position() {
+    let path = script("bad-escape", "var s = \"first line\n  ok \\u{D800}\";\n");
+    let output = rlox().arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error: Unicode escape is not a valid code point at column 6.\n"
+    );
+}